@@ -1,12 +1,29 @@
 use ipnetwork::{IpNetwork, Ipv4Network, Ipv6Network};
 use rfc7239::{parse, Forwarded, NodeIdentifier, NodeName};
-use std::convert::Infallible;
+use std::convert::{Infallible, TryFrom};
+use std::fmt;
 use std::iter::{once, FromIterator, IntoIterator};
 use std::net::{IpAddr, SocketAddr};
 use std::str::FromStr;
 use warp::filters::addr::remote;
 use warp::Filter;
 
+/// Folds an IPv4-mapped IPv6 address (`::ffff:0:0/96`) down to its plain
+/// `Ipv4Addr` form, leaving every other address untouched.
+///
+/// Dual-stack reverse proxies frequently report an IPv4 peer this way, and
+/// without this normalization a trusted-proxy list written in plain IPv4
+/// CIDRs would silently fail to match it.
+fn canonicalize(addr: IpAddr) -> IpAddr {
+    match addr {
+        IpAddr::V6(addr) => addr
+            .to_ipv4_mapped()
+            .map(IpAddr::V4)
+            .unwrap_or(IpAddr::V6(addr)),
+        addr => addr,
+    }
+}
+
 /// Represents a set of IP networks.
 #[derive(Debug, Clone)]
 pub struct IpNetworks {
@@ -15,8 +32,18 @@ pub struct IpNetworks {
 
 impl IpNetworks {
     /// Checks if addr is part of any IP networks included.
+    ///
+    /// IPv4-mapped IPv6 addresses are canonicalized to their `Ipv4Addr` form
+    /// before matching, so trusted-proxy lists written as plain IPv4 CIDRs
+    /// still match peers reported in their dual-stack IPv6 representation.
     pub fn contains(&self, addr: &IpAddr) -> bool {
-        self.networks.iter().any(|&network| network.contains(*addr))
+        let addr = canonicalize(*addr);
+        self.networks.iter().any(|&network| network.contains(addr))
+    }
+
+    /// Returns `true` if this set contains no networks.
+    pub fn is_empty(&self) -> bool {
+        self.networks.is_empty()
     }
 
     /// Special constructor that builds IpNetwork from an iterator of IP addresses.
@@ -50,6 +77,107 @@ impl FromIterator<IpNetwork> for IpNetworks {
     }
 }
 
+/// Error returned when a string is neither valid CIDR notation (`10.0.0.0/8`)
+/// nor a bare IP address.
+#[derive(Debug)]
+pub struct ParseNetworkError(String);
+
+impl fmt::Display for ParseNetworkError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid CIDR notation or IP address: {}", self.0)
+    }
+}
+
+impl std::error::Error for ParseNetworkError {}
+
+/// Parses either CIDR notation (`10.0.0.0/8`, `2001:db8::/32`) or a bare IP
+/// address, the latter treated as a `/32` or `/128` host network.
+fn parse_network(s: &str) -> Result<IpNetwork, ParseNetworkError> {
+    // `IpNetwork::from_str` already treats a bare address (no `/prefix`) as a
+    // full-length host network, so it alone covers both CIDR notation and
+    // bare IPv4/IPv6 addresses.
+    IpNetwork::from_str(s).map_err(|_| ParseNetworkError(s.to_string()))
+}
+
+impl FromStr for IpNetworks {
+    type Err = ParseNetworkError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(IpNetworks {
+            networks: vec![parse_network(s)?],
+        })
+    }
+}
+
+impl TryFrom<&str> for IpNetworks {
+    type Error = ParseNetworkError;
+
+    fn try_from(s: &str) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl<'a> FromIterator<&'a str> for IpNetworks {
+    /// Builds an `IpNetworks` from an iterator of CIDR strings (or bare IP
+    /// addresses).
+    ///
+    /// # Panics
+    ///
+    /// Panics if any entry fails to parse as CIDR notation or a bare IP
+    /// address, so a typo in a trusted-proxy or allow/deny list can't
+    /// silently vanish. Use `str::parse` entry-by-entry (or the `serde`
+    /// feature's `Deserialize` impl) if you need to surface a failure
+    /// instead of aborting.
+    fn from_iter<T: IntoIterator<Item = &'a str>>(cidrs: T) -> Self {
+        IpNetworks {
+            networks: cidrs
+                .into_iter()
+                .map(|s| parse_network(s).unwrap_or_else(|e| panic!("{}", e)))
+                .collect(),
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for IpNetworks {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let cidrs = Vec::<String>::deserialize(deserializer)?;
+        cidrs
+            .iter()
+            .map(|s| parse_network(s))
+            .collect::<Result<Vec<IpNetwork>, _>>()
+            .map(|networks| IpNetworks { networks })
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(feature = "dns")]
+impl IpNetworks {
+    /// Resolves each hostname via non-blocking DNS lookup (following the
+    /// `ToSocketAddrs`/`lookup_host` pattern) and collapses every address
+    /// returned for it into a host-scoped `/32`/`/128` network.
+    ///
+    /// This lets trusted-proxy lists track a reverse proxy addressed by DNS
+    /// name (e.g. a load-balancer hostname in a containerized deployment)
+    /// rather than a fixed `IpAddr`.
+    pub async fn resolve(hosts: impl IntoIterator<Item = String>) -> std::io::Result<IpNetworks> {
+        let mut networks = Vec::new();
+        for host in hosts {
+            let addrs = tokio::net::lookup_host((host.as_str(), 0)).await?;
+            networks.extend(addrs.map(|addr| -> IpNetwork {
+                match addr.ip() {
+                    IpAddr::V4(addr) => Ipv4Network::from(addr).into(),
+                    IpAddr::V6(addr) => Ipv6Network::from(addr).into(),
+                }
+            }));
+        }
+        Ok(IpNetworks { networks })
+    }
+}
+
 /// Creates a `Filter` that provides the "real ip" of the connected client.
 ///
 /// This uses the "x-forwarded-for" or "x-real-ip" headers set by reverse proxies.
@@ -77,7 +205,11 @@ pub fn real_ip(
     remote().and(get_forwarded_for()).map(
         move |addr: Option<SocketAddr>, forwarded_for: Vec<IpAddr>| {
             addr.map(|addr| {
-                let hops = forwarded_for.iter().copied().chain(once(addr.ip()));
+                let hops = forwarded_for
+                    .iter()
+                    .copied()
+                    .chain(once(addr.ip()))
+                    .map(canonicalize);
                 for hop in hops.rev() {
                     if !trusted_proxies.contains(&hop) {
                         return hop;
@@ -85,12 +217,68 @@ pub fn real_ip(
                 }
 
                 // all hops were trusted, return the last one
-                forwarded_for.first().copied().unwrap_or(addr.ip())
+                forwarded_for
+                    .first()
+                    .copied()
+                    .map_or_else(|| canonicalize(addr.ip()), canonicalize)
             })
         },
     )
 }
 
+/// Rejection returned by [`restrict`] when the resolved client IP is denied, or
+/// is not present in a non-empty `allow` list, or when no client IP can be
+/// resolved because the request has no remote address.
+#[derive(Debug)]
+pub struct Forbidden;
+
+impl warp::reject::Reject for Forbidden {}
+
+/// Creates a `Filter` that resolves the real client IP exactly as [`real_ip`]
+/// does, then applies an ordered allow/deny policy against it, extracting the
+/// `IpAddr` on success or rejecting the request with [`Forbidden`] otherwise.
+///
+/// The policy is evaluated as: if the address matches any network in `deny`,
+/// reject; else if `allow` is non-empty and the address matches none of it,
+/// reject; otherwise let the request through. A request without a remote
+/// address, for which no client IP can be resolved, is rejected as well.
+///
+/// ## Example
+///
+/// ```no_run
+/// use warp::Filter;
+/// use warp_real_ip::restrict;
+/// use std::net::IpAddr;
+///
+/// let proxy_addr = [127, 10, 0, 1].into();
+/// let allowed_addr = [10, 0, 0, 1].into();
+/// warp::any()
+///     .and(restrict(vec![proxy_addr], vec![allowed_addr], vec![]))
+///     .map(|addr: IpAddr| format!("Hello {}", addr));
+/// ```
+pub fn restrict(
+    trusted_proxies: impl Into<IpNetworks>,
+    allow: impl Into<IpNetworks>,
+    deny: impl Into<IpNetworks>,
+) -> impl Filter<Extract = (IpAddr,), Error = warp::Rejection> + Clone {
+    let allow = allow.into();
+    let deny = deny.into();
+    real_ip(trusted_proxies).and_then(move |addr: Option<IpAddr>| {
+        let allow = allow.clone();
+        let deny = deny.clone();
+        async move {
+            match addr {
+                Some(addr) if deny.contains(&addr) => Err(warp::reject::custom(Forbidden)),
+                Some(addr) if !allow.is_empty() && !allow.contains(&addr) => {
+                    Err(warp::reject::custom(Forbidden))
+                }
+                Some(addr) => Ok(addr),
+                None => Err(warp::reject::custom(Forbidden)),
+            }
+        }
+    })
+}
+
 /// Creates a `Filter` that extracts the ip addresses from the the "forwarded for" chain
 pub fn get_forwarded_for() -> impl Filter<Extract = (Vec<IpAddr>,), Error = Infallible> + Clone {
     warp::header("x-forwarded-for")
@@ -120,6 +308,110 @@ pub fn get_forwarded_for() -> impl Filter<Extract = (Vec<IpAddr>,), Error = Infa
         .unify()
 }
 
+/// A single hop's worth of metadata from an RFC 7239 `Forwarded` header, or
+/// the equivalent reconstructed from the legacy `X-Forwarded-*` headers.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct ForwardedHop {
+    /// The interface where the request came in to the proxy server, from the `by` parameter.
+    pub by: Option<String>,
+    /// The client that initiated the request, from the `for` parameter.
+    pub for_: Option<String>,
+    /// The `Host` request header field as received by the proxy, from the `host` parameter.
+    pub host: Option<String>,
+    /// The protocol used to make the request, from the `proto` parameter.
+    pub proto: Option<String>,
+}
+
+fn node_identifier_to_string(node: &NodeIdentifier) -> String {
+    let name = match &node.name {
+        NodeName::Ip(ip) => ip.to_string(),
+        NodeName::Unknown => "unknown".to_string(),
+        NodeName::Obfuscated(name) => name.to_string(),
+    };
+    match &node.port {
+        // IPv6 addresses must be bracketed when followed by a port, or the
+        // trailing `:port` becomes ambiguous with the address's own colons.
+        Some(port) if matches!(node.name, NodeName::Ip(IpAddr::V6(_))) => {
+            format!("[{}]:{}", name, port)
+        }
+        Some(port) => format!("{}:{}", name, port),
+        None => name,
+    }
+}
+
+impl From<Forwarded<'_>> for ForwardedHop {
+    fn from(forwarded: Forwarded<'_>) -> Self {
+        ForwardedHop {
+            by: forwarded.forwarded_by.as_ref().map(node_identifier_to_string),
+            for_: forwarded.forwarded_for.as_ref().map(node_identifier_to_string),
+            host: forwarded.host.map(|host| host.to_string()),
+            proto: forwarded.protocol.map(|proto| proto.to_string()),
+        }
+    }
+}
+
+fn optional_header(
+    name: &'static str,
+) -> impl Filter<Extract = (Option<String>,), Error = Infallible> + Clone {
+    warp::header::<String>(name)
+        .map(Some)
+        .or(warp::any().map(|| None))
+        .unify()
+}
+
+/// Creates a `Filter` that extracts the full chain of proxy metadata carried
+/// by the `Forwarded` header: `by`, `for`, `host` and `proto` for each hop.
+///
+/// When the `Forwarded` header is absent, falls back to the legacy headers:
+/// one hop per address from `X-Forwarded-For`/`X-Real-Ip` (for `for`), each
+/// carrying the `X-Forwarded-Proto` (for `proto`) and `X-Forwarded-Host` (for
+/// `host`) values, or a single hop with only `proto` and `host` when no
+/// address is present. The `proto` and `host` values get the same
+/// quoting/bracketing normalization used when parsing addresses.
+pub fn get_forwarded() -> impl Filter<Extract = (Vec<ForwardedHop>,), Error = Infallible> + Clone {
+    optional_header("forwarded")
+        .and(get_forwarded_for())
+        .and(optional_header("x-forwarded-proto"))
+        .and(optional_header("x-forwarded-host"))
+        .map(
+            |forwarded_header: Option<String>,
+             forwarded_for: Vec<IpAddr>,
+             proto: Option<String>,
+             host: Option<String>| {
+                if let Some(forwarded_header) = forwarded_header {
+                    return parse(&forwarded_header)
+                        .filter_map(Result::ok)
+                        .map(ForwardedHop::from)
+                        .collect::<Vec<_>>();
+                }
+
+                let proto = proto.map(|p| maybe_bracketed(&maybe_quoted(p)).to_string());
+                let host = host.map(|h| maybe_bracketed(&maybe_quoted(h)).to_string());
+
+                if forwarded_for.is_empty() {
+                    if proto.is_none() && host.is_none() {
+                        return vec![];
+                    }
+                    return vec![ForwardedHop {
+                        proto,
+                        host,
+                        ..Default::default()
+                    }];
+                }
+
+                forwarded_for
+                    .into_iter()
+                    .map(|addr| ForwardedHop {
+                        for_: Some(addr.to_string()),
+                        host: host.clone(),
+                        proto: proto.clone(),
+                        ..Default::default()
+                    })
+                    .collect()
+            },
+        )
+}
+
 enum CommaSeparatedIteratorState {
     Default,
     Quoted,
@@ -250,7 +542,7 @@ pub fn maybe_quoted<T: AsRef<str>>(x: T) -> String {
 }
 
 pub fn maybe_bracketed<'a>(x: &'a str) -> &'a str {
-    if x.as_bytes()[0] == ('[' as u8) && x.as_bytes()[x.len() - 1] == (']' as u8) {
+    if x.len() >= 2 && x.as_bytes()[0] == b'[' && x.as_bytes()[x.len() - 1] == b']' {
         &x[1..x.len() - 1]
     } else {
         x
@@ -279,7 +571,172 @@ impl<T: FromStr> FromStr for CommaSeparated<T> {
 
 #[cfg(test)]
 mod tests {
-    use crate::{CommaSeparatedIterator, maybe_quoted, maybe_bracketed};
+    use crate::{
+        canonicalize, get_forwarded, maybe_bracketed, maybe_quoted, restrict,
+        CommaSeparatedIterator, ForwardedHop, IpNetworks,
+    };
+    use std::iter::FromIterator;
+    use std::net::{IpAddr, SocketAddr};
+    use std::str::FromStr;
+
+    #[test]
+    fn test_canonicalize() {
+        assert_eq!(
+            "192.0.2.1".parse::<IpAddr>().unwrap(),
+            canonicalize("::ffff:192.0.2.1".parse().unwrap())
+        );
+        assert_eq!(
+            "2001:db8::1".parse::<IpAddr>().unwrap(),
+            canonicalize("2001:db8::1".parse().unwrap())
+        );
+        assert_eq!(
+            "192.0.2.1".parse::<IpAddr>().unwrap(),
+            canonicalize("192.0.2.1".parse().unwrap())
+        );
+    }
+
+    #[test]
+    fn test_ip_networks_from_str() {
+        let networks = IpNetworks::from_str("10.0.0.0/8").unwrap();
+        assert!(networks.contains(&"10.1.2.3".parse().unwrap()));
+        assert!(!networks.contains(&"11.0.0.1".parse().unwrap()));
+
+        let networks = IpNetworks::from_str("192.0.2.1").unwrap();
+        assert!(networks.contains(&"192.0.2.1".parse().unwrap()));
+        assert!(!networks.contains(&"192.0.2.2".parse().unwrap()));
+
+        assert!(IpNetworks::from_str("not an address").is_err());
+    }
+
+    #[test]
+    fn test_ip_networks_from_iter_str() {
+        let networks = IpNetworks::from_iter(vec!["10.0.0.0/8", "2001:db8::/32"]);
+        assert!(networks.contains(&"10.1.2.3".parse().unwrap()));
+        assert!(networks.contains(&"2001:db8::1".parse().unwrap()));
+        assert!(!networks.contains(&"192.0.2.1".parse().unwrap()));
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_ip_networks_from_iter_str_panics_on_invalid_entry() {
+        IpNetworks::from_iter(vec!["10.0.0.0/8", "garbage"]);
+    }
+
+    #[cfg(feature = "dns")]
+    #[tokio::test]
+    async fn test_ip_networks_resolve() {
+        let networks = IpNetworks::resolve(vec!["localhost".to_string()])
+            .await
+            .unwrap();
+        assert!(networks.contains(&"127.0.0.1".parse().unwrap()));
+    }
+
+    #[tokio::test]
+    async fn test_restrict_deny_takes_priority_over_allow() {
+        let addr: IpAddr = "10.0.0.1".parse().unwrap();
+        let filter = restrict(Vec::<IpAddr>::new(), vec![addr], vec![addr]);
+        let result = warp::test::request()
+            .remote_addr(SocketAddr::new(addr, 1234))
+            .filter(&filter)
+            .await;
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_restrict_rejects_address_not_in_allow() {
+        let allowed: IpAddr = "10.0.0.1".parse().unwrap();
+        let other: IpAddr = "10.0.0.2".parse().unwrap();
+        let filter = restrict(Vec::<IpAddr>::new(), vec![allowed], Vec::<IpAddr>::new());
+        let result = warp::test::request()
+            .remote_addr(SocketAddr::new(other, 1234))
+            .filter(&filter)
+            .await;
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_restrict_passes_through_with_empty_allow_and_deny() {
+        let addr: IpAddr = "203.0.113.5".parse().unwrap();
+        let filter = restrict(Vec::<IpAddr>::new(), Vec::<IpAddr>::new(), Vec::<IpAddr>::new());
+        let result = warp::test::request()
+            .remote_addr(SocketAddr::new(addr, 1234))
+            .filter(&filter)
+            .await
+            .unwrap();
+        assert_eq!(result, addr);
+    }
+
+    #[tokio::test]
+    async fn test_restrict_rejects_when_no_remote_addr() {
+        let filter = restrict(Vec::<IpAddr>::new(), Vec::<IpAddr>::new(), Vec::<IpAddr>::new());
+        let result = warp::test::request().filter(&filter).await;
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_get_forwarded_header() {
+        let hops = warp::test::request()
+            .header("forwarded", "for=192.0.2.1;proto=https;host=example.com")
+            .filter(&get_forwarded())
+            .await
+            .unwrap();
+        assert_eq!(
+            hops,
+            vec![ForwardedHop {
+                by: None,
+                for_: Some("192.0.2.1".to_string()),
+                host: Some("example.com".to_string()),
+                proto: Some("https".to_string()),
+            }]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_get_forwarded_header_brackets_ipv6_port() {
+        let hops = warp::test::request()
+            .header("forwarded", "by=\"[2001:db8::1]:8080\";for=192.0.2.1")
+            .filter(&get_forwarded())
+            .await
+            .unwrap();
+        assert_eq!(hops[0].by.as_deref(), Some("[2001:db8::1]:8080"));
+    }
+
+    #[tokio::test]
+    async fn test_get_forwarded_legacy_fallback() {
+        let hops = warp::test::request()
+            .header("x-forwarded-for", "192.0.2.1, 198.51.100.2")
+            .header("x-forwarded-proto", "https")
+            .header("x-forwarded-host", "example.com")
+            .filter(&get_forwarded())
+            .await
+            .unwrap();
+        assert_eq!(
+            hops,
+            vec![
+                ForwardedHop {
+                    by: None,
+                    for_: Some("192.0.2.1".to_string()),
+                    host: Some("example.com".to_string()),
+                    proto: Some("https".to_string()),
+                },
+                ForwardedHop {
+                    by: None,
+                    for_: Some("198.51.100.2".to_string()),
+                    host: Some("example.com".to_string()),
+                    proto: Some("https".to_string()),
+                },
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_get_forwarded_no_headers() {
+        let hops = warp::test::request()
+            .filter(&get_forwarded())
+            .await
+            .unwrap();
+        assert_eq!(hops, Vec::<ForwardedHop>::new());
+    }
 
     #[test]
     fn test_comma_separated_iterator() {
@@ -300,6 +757,28 @@ mod tests {
         assert_eq!("abc", maybe_bracketed("[abc]"));
         assert_eq!("[abc", maybe_bracketed("[abc"));
         assert_eq!("abc]", maybe_bracketed("abc]"));
+        assert_eq!("", maybe_bracketed(""));
+        assert_eq!("[", maybe_bracketed("["));
+        assert_eq!("]", maybe_bracketed("]"));
+    }
+
+    #[tokio::test]
+    async fn test_get_forwarded_empty_legacy_headers_does_not_panic() {
+        let hops = warp::test::request()
+            .header("x-forwarded-proto", "")
+            .header("x-forwarded-host", "")
+            .filter(&get_forwarded())
+            .await
+            .unwrap();
+        assert_eq!(
+            hops,
+            vec![ForwardedHop {
+                by: None,
+                for_: None,
+                host: Some("".to_string()),
+                proto: Some("".to_string()),
+            }]
+        );
     }
 
 }